@@ -17,6 +17,7 @@ use device::Queue;
 use memory::Content;
 
 use sync::AccessFlagBits;
+use sync::AccessType;
 use sync::Fence;
 use sync::PipelineStages;
 use sync::Semaphore;
@@ -151,20 +152,25 @@ pub unsafe trait TrackedBuffer<States = StatesManager>: Buffer {
     }
 
     /// Two resources that conflict with each other should return the same key.
-    fn conflict_key(&self, self_offset: usize, self_size: usize, self_write: bool) -> u64 {
-        // TODO: this dummy impl is a quick hack to not modify all the code
-        unimplemented!()
+    ///
+    /// Derived from the Vulkan handle of the underlying `UnsafeBuffer` alone, ignoring
+    /// offset/size and write-ness; `conflicts_buffer`'s own read-after-read check still applies
+    /// once two same-keyed accesses are compared.
+    fn conflict_key(&self, _self_offset: usize, _self_size: usize, _self_write: bool) -> u64 {
+        self.inner().buffer.internal_object() as u64
     }
 
     /// Returns a new state that corresponds to the moment after a slice of the buffer has been
-    /// used in the pipeline. The parameters indicate in which way it has been used.
+    /// used in the pipeline. `prev` and `next` describe in high-level terms how the slice was
+    /// used before and after this transition; use `sync::barrier` to turn them into the
+    /// memory barrier to put inside the returned request.
     ///
     /// If the transition should result in a pipeline barrier, then it must be returned by this
     /// function.
     // TODO: what should be the behavior if `num_command` is equal to the `num_command` of a
     // previous transition?
     fn transition(&self, states: &mut States, num_command: usize, offset: usize, size: usize,
-                  write: bool, stage: PipelineStages, access: AccessFlagBits)
+                  prev: &[AccessType], next: &[AccessType])
                   -> Option<TrackedBufferPipelineBarrierRequest>;
 
     /// Function called when the command buffer builder is turned into a real command buffer.
@@ -206,6 +212,11 @@ pub struct TrackedBufferPipelineBarrierRequest {
 ///
 /// The memory barrier always concerns the buffer that is currently being processed. You can't add
 /// a memory barrier that concerns another resource.
+///
+/// For a buffer allocated with `SharingMode::Exclusive`, moving it between queue families
+/// requires a release barrier on the source queue followed by an acquire barrier on the
+/// destination queue. `source_queue_family` and `destination_queue_family` express that transfer;
+/// leave both at `vk::QUEUE_FAMILY_IGNORED` when no ownership transfer is taking place.
 pub struct TrackedBufferPipelineMemoryBarrierRequest {
     /// Offset of start of the range to flush.
     pub offset: isize,
@@ -215,8 +226,20 @@ pub struct TrackedBufferPipelineMemoryBarrierRequest {
     pub source_access: AccessFlagBits,
     /// Destination accesses.
     pub destination_access: AccessFlagBits,
+    /// Queue family that owns the buffer before the barrier, or `vk::QUEUE_FAMILY_IGNORED` if no
+    /// ownership transfer is needed.
+    pub source_queue_family: u32,
+    /// Queue family that owns the buffer after the barrier, or `vk::QUEUE_FAMILY_IGNORED` if no
+    /// ownership transfer is needed.
+    pub destination_queue_family: u32,
 }
 
+/// Infos required when submitting a `TrackedBuffer` to a queue.
+///
+/// When a buffer is written on one queue family and then read on another, `pre_barrier` should
+/// carry the acquire half of the ownership transfer (matching `destination_queue_family` to the
+/// queue it's being submitted to) and the previous submission's `post_barrier` should carry the
+/// matching release half.
 pub struct TrackedBufferSubmitInfos {
     pub pre_semaphore: Option<(Arc<Semaphore>, PipelineStages)>,
     pub post_semaphore: Option<Arc<Semaphore>>,
@@ -236,10 +259,10 @@ unsafe impl<B: ?Sized, S> TrackedBuffer<S> for Arc<B> where B: TrackedBuffer<S>
 
     #[inline]
     fn transition(&self, states: &mut S, num_command: usize, offset: usize,
-                  size: usize, write: bool, stage: PipelineStages, access: AccessFlagBits)
+                  size: usize, prev: &[AccessType], next: &[AccessType])
                   -> Option<TrackedBufferPipelineBarrierRequest>
     {
-        (**self).transition(states, num_command, offset, size, write, stage, access)
+        (**self).transition(states, num_command, offset, size, prev, next)
     }
 
     #[inline]
@@ -267,10 +290,10 @@ unsafe impl<'a, B: ?Sized, S> TrackedBuffer<S> for &'a B where B: TrackedBuffer<
 
     #[inline]
     fn transition(&self, states: &mut S, num_command: usize, offset: usize,
-                  size: usize, write: bool, stage: PipelineStages, access: AccessFlagBits)
+                  size: usize, prev: &[AccessType], next: &[AccessType])
                   -> Option<TrackedBufferPipelineBarrierRequest>
     {
-        (**self).transition(states, num_command, offset, size, write, stage, access)
+        (**self).transition(states, num_command, offset, size, prev, next)
     }
 
     #[inline]
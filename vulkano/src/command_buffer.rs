@@ -0,0 +1,255 @@
+// Copyright (c) 2016 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+use std::collections::HashMap;
+
+use buffer::sys::UnsafeBuffer;
+use buffer::traits::TrackedBufferPipelineBarrierRequest;
+use buffer::traits::TrackedBufferPipelineMemoryBarrierRequest;
+use sync::AccessFlagBits;
+use sync::PipelineStages;
+use VulkanObject;
+
+/// Accumulates `TrackedBufferPipelineBarrierRequest`s that land on the same command boundary, so
+/// that they can be flushed as a single `vkCmdPipelineBarrier` instead of one call per resource.
+///
+/// This is the batching primitive itself; no command buffer builder in this crate constructs one
+/// yet, so using it to actually cut a draw's barrier count is follow-up work.
+#[derive(Default)]
+pub struct BarrierBatch {
+    groups: HashMap<usize, BarrierGroup>,
+}
+
+struct BarrierGroup {
+    source_stage: PipelineStages,
+    destination_stages: PipelineStages,
+    by_region: bool,
+    memory_barriers: Vec<BufferMemoryBarrier>,
+    // Maps a `UnsafeBuffer`'s Vulkan handle to the indices in `memory_barriers` that concern it,
+    // so that a newly-added range can be checked for overlap against only the relevant entries.
+    per_buffer: HashMap<u64, Vec<usize>>,
+}
+
+/// A single buffer memory barrier that has been grouped for batched submission.
+pub struct BufferMemoryBarrier {
+    /// Vulkan handle of the `UnsafeBuffer` this barrier concerns.
+    pub buffer: u64,
+    /// Offset of start of the range to flush.
+    pub offset: isize,
+    /// Size of the range to flush.
+    pub size: usize,
+    /// Source accesses.
+    pub source_access: AccessFlagBits,
+    /// Destination accesses.
+    pub destination_access: AccessFlagBits,
+    /// Queue family that owns the buffer before the barrier, or `vk::QUEUE_FAMILY_IGNORED`.
+    pub source_queue_family: u32,
+    /// Queue family that owns the buffer after the barrier, or `vk::QUEUE_FAMILY_IGNORED`.
+    pub destination_queue_family: u32,
+}
+
+/// A pipeline barrier assembled by coalescing every `TrackedBufferPipelineBarrierRequest` that
+/// shares the same `after_command_num`, ready to be submitted as a single `vkCmdPipelineBarrier`.
+pub struct FlushedBarrier {
+    /// The number of the command after which the barrier should be placed.
+    pub after_command_num: usize,
+    /// The source pipeline stages of the transition.
+    pub source_stage: PipelineStages,
+    /// The destination pipeline stages of the transition.
+    pub destination_stages: PipelineStages,
+    /// If true, the pipeline barrier is by region.
+    pub by_region: bool,
+    /// The buffer memory barriers to include in the pipeline barrier.
+    pub memory_barriers: Vec<BufferMemoryBarrier>,
+}
+
+impl BarrierBatch {
+    /// Creates a new, empty batch.
+    #[inline]
+    pub fn new() -> BarrierBatch {
+        BarrierBatch::default()
+    }
+
+    /// Adds a `TrackedBufferPipelineBarrierRequest` to the batch. `buffer` is the underlying
+    /// `UnsafeBuffer` that the request's memory barrier, if any, applies to.
+    pub fn add(&mut self, request: TrackedBufferPipelineBarrierRequest, buffer: &UnsafeBuffer) {
+        let TrackedBufferPipelineBarrierRequest {
+            after_command_num,
+            source_stage,
+            destination_stages,
+            by_region,
+            memory_barrier,
+        } = request;
+
+        let group = self.groups.entry(after_command_num).or_insert_with(|| {
+            BarrierGroup {
+                source_stage: PipelineStages::none(),
+                destination_stages: PipelineStages::none(),
+                by_region: true,
+                memory_barriers: Vec::new(),
+                per_buffer: HashMap::new(),
+            }
+        });
+
+        group.source_stage = group.source_stage | source_stage;
+        group.destination_stages = group.destination_stages | destination_stages;
+        group.by_region = group.by_region && by_region;
+
+        if let Some(memory_barrier) = memory_barrier {
+            group.add_memory_barrier(buffer.internal_object() as u64, memory_barrier);
+        }
+    }
+
+    /// Consumes the batch and returns one flushed pipeline barrier per distinct
+    /// `after_command_num`, in increasing command order.
+    pub fn flush(self) -> Vec<FlushedBarrier> {
+        let mut groups: Vec<_> = self.groups.into_iter().collect();
+        groups.sort_by_key(|&(after_command_num, _)| after_command_num);
+
+        groups.into_iter().map(|(after_command_num, group)| {
+            FlushedBarrier {
+                after_command_num: after_command_num,
+                source_stage: group.source_stage,
+                destination_stages: group.destination_stages,
+                by_region: group.by_region,
+                memory_barriers: group.memory_barriers,
+            }
+        }).collect()
+    }
+}
+
+impl BarrierGroup {
+    /// Adds a memory barrier to the group, merging it into an existing entry for the same buffer
+    /// if their `(offset, size)` ranges overlap and both carry the same queue family ownership
+    /// transfer (if any). Barriers that transfer ownership between different queue families are
+    /// never merged together, since doing so would lose one half of the transfer.
+    fn add_memory_barrier(&mut self, buffer: u64, request: TrackedBufferPipelineMemoryBarrierRequest) {
+        let indices = self.per_buffer.entry(buffer).or_insert_with(Vec::new);
+
+        for &index in indices.iter() {
+            let existing = &mut self.memory_barriers[index];
+
+            if existing.source_queue_family == request.source_queue_family &&
+               existing.destination_queue_family == request.destination_queue_family &&
+               ranges_overlap(existing.offset, existing.size, request.offset, request.size)
+            {
+                let (offset, size) = union_range(existing.offset, existing.size,
+                                                  request.offset, request.size);
+                existing.offset = offset;
+                existing.size = size;
+                existing.source_access = existing.source_access | request.source_access;
+                existing.destination_access = existing.destination_access | request.destination_access;
+                return;
+            }
+        }
+
+        indices.push(self.memory_barriers.len());
+        self.memory_barriers.push(BufferMemoryBarrier {
+            buffer: buffer,
+            offset: request.offset,
+            size: request.size,
+            source_access: request.source_access,
+            destination_access: request.destination_access,
+            source_queue_family: request.source_queue_family,
+            destination_queue_family: request.destination_queue_family,
+        });
+    }
+}
+
+#[inline]
+fn ranges_overlap(offset_a: isize, size_a: usize, offset_b: isize, size_b: usize) -> bool {
+    offset_a < offset_b + size_b as isize && offset_b < offset_a + size_a as isize
+}
+
+#[inline]
+fn union_range(offset_a: isize, size_a: usize, offset_b: isize, size_b: usize) -> (isize, usize) {
+    let start = offset_a.min(offset_b);
+    let end = (offset_a + size_a as isize).max(offset_b + size_b as isize);
+    (start, (end - start) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vk;
+
+    fn empty_group() -> BarrierGroup {
+        BarrierGroup {
+            source_stage: PipelineStages::none(),
+            destination_stages: PipelineStages::none(),
+            by_region: true,
+            memory_barriers: Vec::new(),
+            per_buffer: HashMap::new(),
+        }
+    }
+
+    fn memory_barrier(offset: isize, size: usize, source_queue_family: u32,
+                       destination_queue_family: u32)
+                       -> TrackedBufferPipelineMemoryBarrierRequest
+    {
+        TrackedBufferPipelineMemoryBarrierRequest {
+            offset: offset,
+            size: size,
+            source_access: AccessFlagBits { transfer_write: true, .. AccessFlagBits::none() },
+            destination_access: AccessFlagBits { shader_read: true, .. AccessFlagBits::none() },
+            source_queue_family: source_queue_family,
+            destination_queue_family: destination_queue_family,
+        }
+    }
+
+    #[test]
+    fn ranges_overlap_detects_overlap_but_not_touching_ranges() {
+        assert!(ranges_overlap(0, 10, 5, 10));
+        assert!(!ranges_overlap(0, 10, 10, 10));
+        assert!(!ranges_overlap(10, 10, 0, 10));
+    }
+
+    #[test]
+    fn union_range_covers_both_inputs() {
+        assert_eq!(union_range(0, 10, 5, 10), (0, 15));
+    }
+
+    #[test]
+    fn add_memory_barrier_merges_overlapping_same_buffer_ranges() {
+        let mut group = empty_group();
+
+        group.add_memory_barrier(1, memory_barrier(0, 10, vk::QUEUE_FAMILY_IGNORED,
+                                                     vk::QUEUE_FAMILY_IGNORED));
+        group.add_memory_barrier(1, memory_barrier(5, 10, vk::QUEUE_FAMILY_IGNORED,
+                                                     vk::QUEUE_FAMILY_IGNORED));
+
+        assert_eq!(group.memory_barriers.len(), 1);
+        assert_eq!(group.memory_barriers[0].offset, 0);
+        assert_eq!(group.memory_barriers[0].size, 15);
+    }
+
+    #[test]
+    fn add_memory_barrier_keeps_distinct_queue_family_transfers_separate() {
+        let mut group = empty_group();
+
+        group.add_memory_barrier(1, memory_barrier(0, 10, vk::QUEUE_FAMILY_IGNORED,
+                                                     vk::QUEUE_FAMILY_IGNORED));
+        group.add_memory_barrier(1, memory_barrier(0, 10, 0, 1));
+
+        assert_eq!(group.memory_barriers.len(), 2);
+    }
+
+    #[test]
+    fn flush_orders_groups_by_command_number() {
+        let mut groups = HashMap::new();
+        groups.insert(5, empty_group());
+        groups.insert(1, empty_group());
+
+        let batch = BarrierBatch { groups: groups };
+        let flushed = batch.flush();
+
+        assert_eq!(flushed[0].after_command_num, 1);
+        assert_eq!(flushed[1].after_command_num, 5);
+    }
+}
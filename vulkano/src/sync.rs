@@ -0,0 +1,196 @@
+// Copyright (c) 2016 The vulkano developers
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>,
+// at your option. All files in the project carrying such
+// notice may not be copied, modified, or distributed except
+// according to those terms.
+
+use buffer::traits::TrackedBufferPipelineMemoryBarrierRequest;
+use vk;
+
+/// Describes in high-level terms how a buffer is going to be used.
+///
+/// Instead of forcing callers to hand-assemble a `PipelineStages` and `AccessFlagBits` pair for
+/// every access, each variant here names a concrete usage and internally knows which stage and
+/// access mask it maps to. This is modeled on the `AccessType` enum of the `vk-sync` crate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccessType {
+    /// Read as an index buffer for an indexed draw call.
+    IndexBuffer,
+    /// Read as a vertex buffer.
+    VertexBuffer,
+    /// Read as the parameters buffer of an indirect draw or dispatch call.
+    IndirectBuffer,
+    /// Read as a uniform buffer in a compute shader.
+    ComputeShaderReadUniformBuffer,
+    /// Read in a compute shader in a way that isn't a uniform buffer read, for example a storage
+    /// buffer or texel buffer load.
+    ComputeShaderReadOther,
+    /// Written to from a compute shader.
+    ComputeShaderWrite,
+    /// Read as the source of a transfer command such as `vkCmdCopyBuffer`.
+    TransferRead,
+    /// Written to as the destination of a transfer command.
+    TransferWrite,
+    /// Read on the host through a pointer to mapped memory.
+    HostRead,
+    /// Written on the host through a pointer to mapped memory.
+    HostWrite,
+    /// Catch-all for any other kind of read and write access. Produces the most conservative
+    /// barrier possible, so prefer a more specific variant whenever one applies.
+    General,
+}
+
+impl AccessType {
+    /// Returns the pipeline stage and access flags that correspond to this usage.
+    pub fn stage_and_access(&self) -> (PipelineStages, AccessFlagBits) {
+        match *self {
+            AccessType::IndexBuffer => {
+                (PipelineStages { vertex_input: true, .. PipelineStages::none() },
+                 AccessFlagBits { index_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::VertexBuffer => {
+                (PipelineStages { vertex_input: true, .. PipelineStages::none() },
+                 AccessFlagBits { vertex_attribute_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::IndirectBuffer => {
+                (PipelineStages { draw_indirect: true, .. PipelineStages::none() },
+                 AccessFlagBits { indirect_command_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::ComputeShaderReadUniformBuffer => {
+                (PipelineStages { compute_shader: true, .. PipelineStages::none() },
+                 AccessFlagBits { uniform_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::ComputeShaderReadOther => {
+                (PipelineStages { compute_shader: true, .. PipelineStages::none() },
+                 AccessFlagBits { shader_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::ComputeShaderWrite => {
+                (PipelineStages { compute_shader: true, .. PipelineStages::none() },
+                 AccessFlagBits { shader_write: true, .. AccessFlagBits::none() })
+            },
+            AccessType::TransferRead => {
+                (PipelineStages { transfer: true, .. PipelineStages::none() },
+                 AccessFlagBits { transfer_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::TransferWrite => {
+                (PipelineStages { transfer: true, .. PipelineStages::none() },
+                 AccessFlagBits { transfer_write: true, .. AccessFlagBits::none() })
+            },
+            AccessType::HostRead => {
+                (PipelineStages { host: true, .. PipelineStages::none() },
+                 AccessFlagBits { host_read: true, .. AccessFlagBits::none() })
+            },
+            AccessType::HostWrite => {
+                (PipelineStages { host: true, .. PipelineStages::none() },
+                 AccessFlagBits { host_write: true, .. AccessFlagBits::none() })
+            },
+            AccessType::General => {
+                (PipelineStages { all_commands: true, .. PipelineStages::none() },
+                 AccessFlagBits { memory_read: true, memory_write: true, .. AccessFlagBits::none() })
+            },
+        }
+    }
+
+    /// Returns true if this usage writes to the buffer.
+    #[inline]
+    pub fn is_write(&self) -> bool {
+        match *self {
+            AccessType::ComputeShaderWrite |
+            AccessType::TransferWrite |
+            AccessType::HostWrite |
+            AccessType::General => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the union of the pipeline stages used by `accesses`.
+///
+/// Use this to build `TrackedBufferPipelineBarrierRequest::source_stage`/`destination_stages`
+/// from the same `prev`/`next` slices passed to `barrier`.
+pub fn stages(accesses: &[AccessType]) -> PipelineStages {
+    accesses.iter()
+        .map(|a| a.stage_and_access().0)
+        .fold(PipelineStages::none(), |a, b| a | b)
+}
+
+/// Builds the memory barrier needed to transition a buffer from being used the way described by
+/// `prev` to being used the way described by `next`.
+///
+/// The source accesses are the union of `prev`, the destination accesses are the union of
+/// `next`. Returns `None` if every access on both sides is read-only, since a read-after-read
+/// never needs memory to be made visible, only `after_command_num` ordering.
+///
+/// The returned request's `offset` and `size` are left at `0`; callers (typically a
+/// `TrackedBuffer::transition` implementation) are expected to overwrite them with the offset
+/// and size of the access they're building a barrier for.
+pub fn barrier(prev: &[AccessType], next: &[AccessType])
+                -> Option<TrackedBufferPipelineMemoryBarrierRequest>
+{
+    if !prev.iter().any(AccessType::is_write) && !next.iter().any(AccessType::is_write) {
+        return None;
+    }
+
+    let source_access = prev.iter()
+        .map(|a| a.stage_and_access().1)
+        .fold(AccessFlagBits::none(), |a, b| a | b);
+    let destination_access = next.iter()
+        .map(|a| a.stage_and_access().1)
+        .fold(AccessFlagBits::none(), |a, b| a | b);
+
+    Some(TrackedBufferPipelineMemoryBarrierRequest {
+        offset: 0,
+        size: 0,
+        source_access: source_access,
+        destination_access: destination_access,
+        source_queue_family: vk::QUEUE_FAMILY_IGNORED,
+        destination_queue_family: vk::QUEUE_FAMILY_IGNORED,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stage_and_access_maps_index_buffer() {
+        let (stage, access) = AccessType::IndexBuffer.stage_and_access();
+        assert!(stage.vertex_input);
+        assert!(access.index_read);
+    }
+
+    #[test]
+    fn is_write_only_for_write_variants() {
+        assert!(!AccessType::IndexBuffer.is_write());
+        assert!(!AccessType::ComputeShaderReadOther.is_write());
+        assert!(AccessType::ComputeShaderWrite.is_write());
+        assert!(AccessType::TransferWrite.is_write());
+        assert!(AccessType::HostWrite.is_write());
+        assert!(AccessType::General.is_write());
+    }
+
+    #[test]
+    fn stages_unions_across_accesses() {
+        let s = stages(&[AccessType::IndexBuffer, AccessType::TransferRead]);
+        assert!(s.vertex_input);
+        assert!(s.transfer);
+    }
+
+    #[test]
+    fn barrier_elides_read_after_read() {
+        assert!(barrier(&[AccessType::IndexBuffer], &[AccessType::VertexBuffer]).is_none());
+    }
+
+    #[test]
+    fn barrier_present_when_either_side_writes() {
+        let request = barrier(&[AccessType::TransferWrite], &[AccessType::ComputeShaderReadOther])
+            .expect("a memory barrier is required after a write");
+        assert!(request.source_access.transfer_write);
+        assert!(request.destination_access.shader_read);
+        assert_eq!(request.source_queue_family, vk::QUEUE_FAMILY_IGNORED);
+        assert_eq!(request.destination_queue_family, vk::QUEUE_FAMILY_IGNORED);
+    }
+}